@@ -1,5 +1,7 @@
 #![allow(dead_code)]
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 // ┏━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┓
@@ -7,6 +9,7 @@ use uuid::Uuid;
 // ┗━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛
 
 // --- Meta ---
+#[derive(Clone, Serialize, Deserialize)]
 struct NodeMeta {
   id: Uuid,
   name: String,
@@ -24,20 +27,35 @@ impl NodeMeta {
 }
 
 // Node
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Node {
   Entry(Entry),
   Container(Container),
 }
 
 impl Node {
-  pub fn get_entry(&mut self) -> Option<&mut Entry> {
+  pub fn get_entry(&self) -> Option<&Entry> {
     match self {
       Node::Entry(e) => Some(e),
       _ => None,
     }
   }
 
-  pub fn get_container(&mut self) -> Option<&mut Container> {
+  pub fn get_entry_mut(&mut self) -> Option<&mut Entry> {
+    match self {
+      Node::Entry(e) => Some(e),
+      _ => None,
+    }
+  }
+
+  pub fn get_container(&self) -> Option<&Container> {
+    match self {
+      Node::Container(c) => Some(c),
+      _ => None,
+    }
+  }
+
+  pub fn get_container_mut(&mut self) -> Option<&mut Container> {
     match self {
       Node::Container(c) => Some(c),
       _ => None,
@@ -91,7 +109,7 @@ impl Node {
 // --- Data ---
 
 // Entry state
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum EntryState {
   Pending,
   InProgress,
@@ -124,6 +142,7 @@ impl EntryState {
 }
 
 // Entry
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Entry {
   meta: NodeMeta,
   state: EntryState,
@@ -138,215 +157,1035 @@ impl Entry {
   }
 }
 
-// Container
+// Container. Child order isn't stored here anymore - it lives in the
+// arena's first-child/next-sibling links (see `ArenaNode`), so a Container
+// is just a named node that's allowed to have children.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Container {
   meta: NodeMeta,
-  order: Vec<Uuid>,
 }
 
 impl Container {
   pub fn new<S: Into<String>>(name: S, desc: S) -> Self {
     Self {
       meta: NodeMeta::new(name.into(), desc.into()),
-      order: Vec::<Uuid>::new(),
     }
   }
+}
+
+// --- Arena ---
+
+// A stable, reusable slot index into a `Version`'s arena. Never exposed
+// outside this module - external code only ever deals in `Uuid`s, which
+// `Version::by_uuid` resolves to a `NodeId` for the current version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NodeId(usize);
+
+// One arena slot: the node's own data plus its position in the tree,
+// expressed the way `slab_tree`/`indextree` do it - a parent link and a
+// singly-linked first-child/next-sibling chain instead of a `Vec` per
+// container. Traversal is pointer-chasing through these links; there's no
+// separate ordering structure that could drift out of sync with them.
+#[derive(Clone)]
+struct ArenaNode {
+  value: Node,
+  parent: Option<NodeId>,
+  first_child: Option<NodeId>,
+  next_sibling: Option<NodeId>,
+}
+
+// --- History ---
+
+// One immutable snapshot of the tree. Arena slots are `Rc`-shared with
+// neighbouring versions; a mutating `Tree` operation clones this struct and
+// then path-copies just the slots it actually touches via `Rc::make_mut`,
+// so untouched *node payloads* stay shared with every other version that
+// held them. The `arena`/`by_uuid` index structures are still copied in
+// full on every clone - real structural sharing there would need an
+// immutable, path-copying index (e.g. a HAMT) instead of a `Vec`/`HashMap`.
+#[derive(Clone)]
+struct Version {
+  root: NodeId,
+  arena: Vec<Option<Rc<ArenaNode>>>,
+  by_uuid: HashMap<Uuid, NodeId>,
+  free: Vec<NodeId>,
+}
+
+impl Version {
+  fn node_id(&self, uuid: &Uuid) -> Result<NodeId, String> {
+    self.by_uuid.get(uuid).copied()
+      .ok_or_else(|| format!("Node {} not found", uuid))
+  }
+
+  fn slot(&self, id: NodeId) -> &ArenaNode {
+    self.arena[id.0].as_deref().expect("dangling NodeId")
+  }
+
+  // Path-copies the slot's value into this version (a clone, only if it
+  // isn't already uniquely owned here) and hands back a mutable handle.
+  fn slot_mut(&mut self, id: NodeId) -> &mut ArenaNode {
+    Rc::make_mut(self.arena[id.0].as_mut().expect("dangling NodeId"))
+  }
+
+  // Reuses a freed slot if one is available, otherwise grows the arena -
+  // the classic slab allocation pattern.
+  fn alloc(&mut self, arena_node: ArenaNode) -> NodeId {
+    if let Some(id) = self.free.pop() {
+      self.arena[id.0] = Some(Rc::new(arena_node));
+      return id;
+    }
+
+    let id = NodeId(self.arena.len());
+    self.arena.push(Some(Rc::new(arena_node)));
+    id
+  }
+
+  fn free_slot(&mut self, id: NodeId) {
+    self.arena[id.0] = None;
+    self.free.push(id);
+  }
+
+  // Frees `id`'s entire subtree - every descendant's slot and `by_uuid`
+  // entry, not just `id`'s own. Leaving a descendant's slot allocated with
+  // `parent: Some(id)` pointing at a freed, eventually-reused slot is how
+  // `remove_node` used to silently corrupt unrelated nodes (see `alloc`).
+  fn remove_subtree(&mut self, id: NodeId) {
+    for child in self.children(id) {
+      self.remove_subtree(child);
+    }
+
+    let uuid = *self.slot(id).value.get_id();
+    self.by_uuid.remove(&uuid);
+    self.free_slot(id);
+  }
+
+  // `parent`'s children in order, following the first-child/next-sibling
+  // chain - the pointer-chasing equivalent of the old `Container::order`.
+  fn children(&self, parent: NodeId) -> Vec<NodeId> {
+    let mut out = Vec::new();
+    let mut cursor = self.slot(parent).first_child;
+
+    while let Some(id) = cursor {
+      out.push(id);
+      cursor = self.slot(id).next_sibling;
+    }
+
+    out
+  }
+
+  // Rewrites `parent`'s child chain to match `ordered`, path-copying only
+  // the slots whose `first_child`/`next_sibling` link actually changes.
+  fn relink_children(&mut self, parent: NodeId, ordered: &[NodeId]) {
+    let first = ordered.first().copied();
+    if self.slot(parent).first_child != first {
+      self.slot_mut(parent).first_child = first;
+    }
+
+    for pair in ordered.windows(2) {
+      if self.slot(pair[0]).next_sibling != Some(pair[1]) {
+        self.slot_mut(pair[0]).next_sibling = Some(pair[1]);
+      }
+    }
+
+    if let Some(&last) = ordered.last() {
+      if self.slot(last).next_sibling.is_some() {
+        self.slot_mut(last).next_sibling = None;
+      }
+    }
+  }
+
+  fn get_node(&self, node_id: &Uuid) -> Result<&Node, String> {
+    let id = self.node_id(node_id)?;
+    Ok(&self.slot(id).value)
+  }
+
+  fn get_node_mut(&mut self, node_id: &Uuid) -> Result<&mut Node, String> {
+    let id = self.node_id(node_id)?;
+    Ok(&mut self.slot_mut(id).value)
+  }
+
+  fn get_entry(&self, node_id: &Uuid) -> Result<&Entry, String> {
+    let entry = self.get_node(node_id)?
+      .get_entry()
+      .ok_or_else(|| format!("Node {} must be an entry", node_id))?;
+
+    Ok(entry)
+  }
+
+  fn get_entry_mut(&mut self, node_id: &Uuid) -> Result<&mut Entry, String> {
+    let entry = self.get_node_mut(node_id)?
+      .get_entry_mut()
+      .ok_or_else(|| format!("Node {} must be an entry", node_id))?;
+
+    Ok(entry)
+  }
+
+  fn get_parent_id(&self, node_id: &Uuid) -> Result<&Uuid, String> {
+    let id = self.node_id(node_id)?;
+    let parent_id = self.slot(id).parent
+      .ok_or_else(|| "Root node doesn't have parent container".to_string())?;
+
+    Ok(self.slot(parent_id).value.get_id())
+  }
 
-  fn add_order(&mut self, id: &Uuid) {
-    self.order.push(*id);
+  fn compare_parents(&self, node_id1: &Uuid, node_id2: &Uuid) -> Result<bool, String> {
+    let id1 = self.node_id(node_id1)?;
+    let id2 = self.node_id(node_id2)?;
+
+    Ok(self.slot(id1).parent == self.slot(id2).parent)
   }
 
-  fn remove_order(&mut self, id: &Uuid) {
-    self.order.retain(|x| x != id);
+  // Builds a fresh arena from a plain node/children table - used by
+  // `Tree::merge` once it has replayed an op log into that shape. Walks
+  // the tree depth-first, reserving each node's slot before recursing into
+  // its children so a child can always name its parent's `NodeId`.
+  fn from_tree(root_id: Uuid, mut nodes: HashMap<Uuid, Node>, children: HashMap<Uuid, Vec<Uuid>>) -> Result<Version, String> {
+    let mut arena = Vec::new();
+    let mut by_uuid = HashMap::new();
+
+    let root = Version::build_subtree(root_id, None, &mut nodes, &children, &mut arena, &mut by_uuid)?;
+
+    Ok(Version { root, arena, by_uuid, free: Vec::new() })
   }
 
-  fn move_order(&mut self, id: &Uuid, pos: usize) {
-    self.order.retain(|x| x != id);
-    self.order.insert(pos, *id);
+  fn build_subtree(
+    uuid: Uuid,
+    parent: Option<NodeId>,
+    nodes: &mut HashMap<Uuid, Node>,
+    children: &HashMap<Uuid, Vec<Uuid>>,
+    arena: &mut Vec<Option<Rc<ArenaNode>>>,
+    by_uuid: &mut HashMap<Uuid, NodeId>,
+  ) -> Result<NodeId, String> {
+    let value = nodes.remove(&uuid).ok_or_else(|| format!("Node {} missing during merge replay", uuid))?;
+    let id = NodeId(arena.len());
+    arena.push(None);
+    by_uuid.insert(uuid, id);
+
+    let child_uuids = children.get(&uuid).cloned().unwrap_or_default();
+    let mut child_ids = Vec::with_capacity(child_uuids.len());
+
+    for child_uuid in child_uuids {
+      child_ids.push(Version::build_subtree(child_uuid, Some(id), nodes, children, arena, by_uuid)?);
+    }
+
+    arena[id.0] = Some(Rc::new(ArenaNode {
+      value,
+      parent,
+      first_child: child_ids.first().copied(),
+      next_sibling: None,
+    }));
+
+    for pair in child_ids.windows(2) {
+      let slot = arena[pair[0].0].as_mut().expect("just built");
+      Rc::get_mut(slot).expect("freshly built node is uniquely owned").next_sibling = Some(pair[1]);
+    }
+
+    Ok(id)
   }
 
-  fn swap_order(&mut self, id1: &Uuid, id2: &Uuid) {
-    if let (Some(i), Some(j)) = (
-      self.order.iter().position(|x| x == id1),
-      self.order.iter().position(|y| y == id2),
-    ) {
-      self.order.swap(i, j);
+  fn accumulate_progress(&self, id: NodeId, progress: &mut Progress) {
+    for child in self.children(id) {
+      match &self.slot(child).value {
+        Node::Entry(entry) => match entry.state {
+          EntryState::Pending => progress.pending += 1,
+          EntryState::InProgress => progress.in_progress += 1,
+          EntryState::Completed => progress.completed += 1,
+          EntryState::Canceled => progress.canceled += 1,
+        },
+        Node::Container(_) => self.accumulate_progress(child, progress),
+      }
     }
   }
 }
 
+// --- Progress ---
+
+// Per-state tally over a container's subtree, as returned by
+// `Tree::container_progress`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Progress {
+  pub pending: usize,
+  pub in_progress: usize,
+  pub completed: usize,
+  pub canceled: usize,
+}
+
+impl Progress {
+  // (completed, total) over non-canceled leaf entries, e.g. for a "7/12"
+  // or percentage display - canceled entries are excluded from both sides
+  // rather than counted as incomplete.
+  pub fn ratio(&self) -> (usize, usize) {
+    let total = self.pending + self.in_progress + self.completed;
+    (self.completed, total)
+  }
+}
+
+// How `Tree::sort_children` should order a container's children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+  State,
+  Name,
+  Manual,
+}
+
+// Pending/InProgress sort before Completed/Canceled. Containers don't carry
+// a state of their own, so they rank alongside the still-active group.
+fn state_rank(node: &Node) -> u8 {
+  match node {
+    Node::Entry(entry) => match entry.state {
+      EntryState::Pending | EntryState::InProgress => 0,
+      EntryState::Completed | EntryState::Canceled => 1,
+    },
+    Node::Container(_) => 0,
+  }
+}
+
 // Tree
 pub struct Tree {
-  root: Uuid,
-  nodes: HashMap<Uuid, Node>,
-  locations: HashMap<Uuid, Uuid>
+  history: Vec<Version>,
+  cursor: usize,
+  actor: Uuid,
+  counter: u64,
+  log: Vec<Op>,
+  // `log_marks[i]` is `log.len()` as of `history[i]` - lets `commit` prune
+  // a discarded redo branch's ops out of `log`, and lets `active_log` find
+  // just the ops behind the current cursor, without replaying anything.
+  log_marks: Vec<usize>,
 }
 
 impl Tree {
   pub fn new(root: Container) -> Self {
     let root_node = Node::Container(root);
-    let root_id = *root_node.get_id();
+    let root_uuid = *root_node.get_id();
+    let root_id = NodeId(0);
 
-    let mut nodes = HashMap::<Uuid, Node>::new();
-    nodes.insert(root_id, root_node);
+    let arena_node = ArenaNode {
+      value: root_node,
+      parent: None,
+      first_child: None,
+      next_sibling: None,
+    };
 
-    Self {
+    let mut by_uuid = HashMap::new();
+    by_uuid.insert(root_uuid, root_id);
+
+    let version = Version {
       root: root_id,
-      nodes,
-      locations: HashMap::<Uuid, Uuid>::new(),
+      arena: vec![Some(Rc::new(arena_node))],
+      by_uuid,
+      free: Vec::new(),
+    };
+
+    Self {
+      history: vec![version],
+      cursor: 0,
+      actor: Uuid::new_v4(),
+      counter: 0,
+      log: Vec::new(),
+      log_marks: vec![0],
     }
   }
 
-  // Utility
-  pub fn get_node(&mut self, node_id: &Uuid) -> Result<&mut Node, String> {
-    let node = self.nodes.get_mut(node_id)
-      .ok_or_else(|| format!("Node {} not found", node_id))?;
+  // Stamps the next local op with a Lamport counter plus this tree's actor
+  // id - the `(counter, actor)` pair `merge` later uses as a total order.
+  fn next_op_id(&mut self) -> OpId {
+    self.counter += 1;
+    OpId { counter: self.counter, actor: self.actor }
+  }
 
-    Ok(node)
+  fn current(&self) -> &Version {
+    &self.history[self.cursor]
   }
 
-  fn get_entry(&mut self, node_id: &Uuid) -> Result<&mut Entry, String> {
-    let entry = self.get_node(node_id)?
-      .get_entry()
-      .ok_or_else(|| format!("Node {} must be an entry", node_id))?;
+  // Starting point for a mutating operation: a clone of the current version
+  // to path-copy slots into before `commit` makes it the new head. Node
+  // payloads are shared via `Rc` and only path-copied where touched, but the
+  // `arena`/`by_uuid` index structures themselves are still copied whole
+  // here - O(node count), not O(path depth).
+  fn next_version(&self) -> Version {
+    self.current().clone()
+  }
 
-    Ok(entry)
+  // Drops everything past the current cursor - the redo branch a fresh edit
+  // discards. `history` already worked this way; extended to `log`/
+  // `log_marks` too, so an edit made after `undo` doesn't leave the undone
+  // ops' ids lingering in `log` for `save`/`merge` to resurrect.
+  fn drop_redo_branch(&mut self) {
+    self.history.truncate(self.cursor + 1);
+    self.log.truncate(self.log_marks[self.cursor]);
+    self.log_marks.truncate(self.cursor + 1);
   }
 
-  fn get_container(&mut self, node_id: &Uuid) -> Result<&mut Container, String> {
-    let container = self.get_node(node_id)?
-      .get_container()
-      .ok_or_else(|| format!("Node {} must be a container", node_id))?;
+  // `history.push`/`log_marks.push`/`cursor += 1`, the common tail of
+  // `commit` and `merge` once `log` already holds the right ops.
+  fn finish_commit(&mut self, version: Version) {
+    self.history.push(version);
+    self.log_marks.push(self.log.len());
+    self.cursor += 1;
+  }
 
-    Ok(container)
+  // Makes `version` the new current version, appending `ops` to the log
+  // behind it.
+  fn commit(&mut self, version: Version, ops: Vec<Op>) {
+    self.drop_redo_branch();
+    self.log.extend(ops);
+    self.finish_commit(version);
   }
 
-  pub fn get_parent_id(&self, node_id: &Uuid) -> Result<&Uuid, String> {
-    if *node_id == self.root {Err("Root node doesn't have parent container")?}
+  // The ops behind the current version - i.e. `log` minus whatever's left
+  // of a redo branch `undo` backed away from but no edit has pruned yet.
+  // This is what `save`/`merge` should replay, not the raw `log`.
+  fn active_log(&self) -> &[Op] {
+    &self.log[..self.log_marks[self.cursor]]
+  }
 
-    let parent_id = self.locations.get(node_id)
-      .ok_or_else(|| "Parent id not found".to_string())?;
+  // An independent copy of this tree for offline editing - same history and
+  // log, but a fresh actor id, so its future ops carry `(counter, actor)`
+  // pairs that don't collide with this tree's once the two are `merge`d
+  // back together.
+  pub fn fork(&self) -> Self {
+    Self {
+      history: self.history.clone(),
+      cursor: self.cursor,
+      actor: Uuid::new_v4(),
+      counter: self.counter,
+      log: self.log.clone(),
+      log_marks: self.log_marks.clone(),
+    }
+  }
+
+  pub fn undo(&mut self) -> Result<(), String> {
+    if self.cursor == 0 {Err("Nothing to undo")?}
 
-    Ok(parent_id)
+    self.cursor -= 1;
+    Ok(())
   }
 
-  pub fn get_parent_node(&mut self, node_id: &Uuid) -> Result <&mut Node, String> {
-    let parent_id = *self.get_parent_id(node_id)?;
+  pub fn redo(&mut self) -> Result<(), String> {
+    if self.cursor + 1 >= self.history.len() {Err("Nothing to redo")?}
 
-    let parent_node = self.get_node(&parent_id)?;
-    Ok(parent_node)
+    self.cursor += 1;
+    Ok(())
   }
 
-  fn get_parent_container(&mut self, node_id: &Uuid) -> Result<&mut Container, String> {
-    let parent_id = *self.get_parent_id(node_id)?;
+  // Utility
+  pub fn get_root_id(&self) -> &Uuid {
+    let version = self.current();
+    version.slot(version.root).value.get_id()
+  }
 
-    let container = self.get_container(&parent_id)?;
-    Ok(container)
+  pub fn get_node(&self, node_id: &Uuid) -> Result<&Node, String> {
+    self.current().get_node(node_id)
   }
 
-  fn compare_parents(&self, node_id1: &Uuid, node_id2: &Uuid) -> Result<bool, String> {
-    let parent_id1 = self.get_parent_id(node_id1)?;
-    let parent_id2 = self.get_parent_id(node_id2)?;
+  fn get_entry(&self, node_id: &Uuid) -> Result<&Entry, String> {
+    self.current().get_entry(node_id)
+  }
+
+  pub fn get_parent_id(&self, node_id: &Uuid) -> Result<&Uuid, String> {
+    self.current().get_parent_id(node_id)
+  }
 
-    Ok(parent_id1 == parent_id2)
+  pub fn get_parent_node(&self, node_id: &Uuid) -> Result<&Node, String> {
+    let parent_id = *self.get_parent_id(node_id)?;
+    self.get_node(&parent_id)
   }
 
   // --- The interesting part ---
 
   // Basic Nodes operations
   pub fn add_node(&mut self, parent_id: &Uuid, node: Node) -> Result<(), String> {
-    let node_id = *node.get_id();
+    let mut version = self.next_version();
+
+    let parent = version.node_id(parent_id)?;
+    if !version.slot(parent).value.is_container() {Err(format!("Node {} must be a container", parent_id))?}
+
+    let mut children = version.children(parent);
+    let after = children.last().map(|&id| *version.slot(id).value.get_id());
 
-    let container = self.get_container(parent_id)?;
-    container.add_order(&node_id);
+    let node_uuid = *node.get_id();
+    let op = Op::AddNode {
+      id: self.next_op_id(),
+      parent: *parent_id,
+      after,
+      node_id: node_uuid,
+      node: node.clone(),
+    };
 
-    self.nodes.insert(node_id, node);
-    self.locations.insert(node_id, *parent_id);
+    let new_id = version.alloc(ArenaNode {
+      value: node,
+      parent: Some(parent),
+      first_child: None,
+      next_sibling: None,
+    });
 
+    children.push(new_id);
+    version.relink_children(parent, &children);
+
+    version.by_uuid.insert(node_uuid, new_id);
+
+    self.commit(version, vec![op]);
     Ok(())
   }
 
+  // Removes `node_id` and, if it's a container, everything under it - a
+  // dangling `parent: Some(freed_NodeId)` left on an orphaned child would
+  // otherwise point at whatever `alloc` hands that slot to next.
   pub fn remove_node(&mut self, node_id: &Uuid) -> Result<(), String> {
-    let container = self.get_parent_container(node_id)?;
-    container.remove_order(node_id);
+    let mut version = self.next_version();
+
+    let id = version.node_id(node_id)?;
+    let parent = version.slot(id).parent
+      .ok_or_else(|| "Root node doesn't have parent container".to_string())?;
 
-    self.nodes.remove(node_id);
-    self.locations.remove(node_id);
+    let mut children = version.children(parent);
+    children.retain(|&child| child != id);
+    version.relink_children(parent, &children);
 
+    version.remove_subtree(id);
+
+    let op = Op::Remove { id: self.next_op_id(), node_id: *node_id };
+
+    self.commit(version, vec![op]);
     Ok(())
   }
 
   pub fn move_node(&mut self, node_id: &Uuid, new_pos: usize) -> Result<(), String> {
-    let container = self.get_parent_container(node_id)?;
-    container.move_order(node_id, new_pos);
+    let mut version = self.next_version();
+
+    let id = version.node_id(node_id)?;
+    let parent = version.slot(id).parent
+      .ok_or_else(|| "Root node doesn't have parent container".to_string())?;
+
+    let mut children = version.children(parent);
+    children.retain(|&child| child != id);
+    children.insert(new_pos, id);
+    version.relink_children(parent, &children);
 
+    let after = if new_pos == 0 {
+      None
+    } else {
+      Some(*version.slot(children[new_pos - 1]).value.get_id())
+    };
+    let op = Op::Reorder { id: self.next_op_id(), node_id: *node_id, after };
+
+    self.commit(version, vec![op]);
     Ok(())
   }
 
   pub fn swap_nodes(&mut self, node_id1: &Uuid, node_id2: &Uuid) -> Result<(), String> {
-    if !self.compare_parents(node_id1, node_id2)? {Err("Can only swap nodes with the same parent")?}
+    if !self.current().compare_parents(node_id1, node_id2)? {Err("Can only swap nodes with the same parent")?}
     if node_id1 == node_id2 {Err("Cannot swap a node with itself")?}
 
-    let container = self.get_parent_container(node_id1)?;
-    container.swap_order(node_id1, node_id2);
+    let mut version = self.next_version();
+
+    let id1 = version.node_id(node_id1)?;
+    let id2 = version.node_id(node_id2)?;
+    let parent = version.slot(id1).parent
+      .ok_or_else(|| "Root node doesn't have parent container".to_string())?;
+
+    let mut children = version.children(parent);
+    let (i, j) = (
+      children.iter().position(|&id| id == id1).expect("node missing from its own parent's children"),
+      children.iter().position(|&id| id == id2).expect("node missing from its own parent's children"),
+    );
+    children.swap(i, j);
+    version.relink_children(parent, &children);
 
+    let after1 = if j == 0 {None} else {Some(*version.slot(children[j - 1]).value.get_id())};
+    let after2 = if i == 0 {None} else {Some(*version.slot(children[i - 1]).value.get_id())};
+    let op1 = Op::Reorder { id: self.next_op_id(), node_id: *node_id1, after: after1 };
+    let op2 = Op::Reorder { id: self.next_op_id(), node_id: *node_id2, after: after2 };
+
+    self.commit(version, vec![op1, op2]);
     Ok(())
   }
 
   pub fn change_parent(&mut self, new_parent_id: &Uuid, node_id: &Uuid) -> Result<(), String> {
-    let parent_id = self.get_parent_id(node_id)?;
-    if parent_id == new_parent_id {Err(format!("Node already is inside of container {}", parent_id))?}
+    let current = self.current();
+    let id = current.node_id(node_id)?;
+    let old_parent = current.slot(id).parent
+      .ok_or_else(|| "Root node doesn't have parent container".to_string())?;
+    let old_parent_uuid = *current.slot(old_parent).value.get_id();
 
-    {
-      let container = self.get_parent_container(node_id)?;
-      container.remove_order(node_id);
-    }
+    if old_parent_uuid == *new_parent_id {Err(format!("Node already is inside of container {}", old_parent_uuid))?}
+
+    let mut version = self.next_version();
+
+    let new_parent = version.node_id(new_parent_id)?;
+    if !version.slot(new_parent).value.is_container() {Err(format!("Node {} must be a container", new_parent_id))?}
 
-    {
-      let new_container = self.get_container(new_parent_id)?;
-      new_container.add_order(node_id);
+    let mut ancestor = Some(new_parent);
+    while let Some(a) = ancestor {
+      if a == id {Err("Cannot move a node into its own subtree")?}
+      ancestor = version.slot(a).parent;
     }
 
-    self.locations.remove(node_id);
-    self.locations.insert(*node_id, *new_parent_id);
+    let mut old_children = version.children(old_parent);
+    old_children.retain(|&child| child != id);
+    version.relink_children(old_parent, &old_children);
+
+    let mut new_children = version.children(new_parent);
+    let after = new_children.last().map(|&cid| *version.slot(cid).value.get_id());
+    new_children.push(id);
+    version.relink_children(new_parent, &new_children);
+
+    version.slot_mut(id).parent = Some(new_parent);
+
+    let op = Op::Reparent { id: self.next_op_id(), node_id: *node_id, new_parent: *new_parent_id, after };
 
+    self.commit(version, vec![op]);
     Ok(())
   }
 
-  pub fn get_children_ids(&mut self, parent_id: &Uuid) -> Result<Vec<&Uuid>, String> {
-    let container = self.get_container(parent_id)?;
+  pub fn get_children_ids(&self, parent_id: &Uuid) -> Result<Vec<&Uuid>, String> {
+    let version = self.current();
+    let parent = version.node_id(parent_id)?;
+
+    if !version.slot(parent).value.is_container() {Err(format!("Node {} must be a container", parent_id))?}
+
+    let children = version.children(parent).into_iter()
+      .map(|id| version.slot(id).value.get_id())
+      .collect();
 
-    let children = container.order.iter().collect();
     Ok(children)
   }
 
   // Entry Node operation
-  pub fn entry_state(&mut self, node_id: &Uuid) -> Result<&EntryState, String> {
+  pub fn entry_state(&self, node_id: &Uuid) -> Result<&EntryState, String> {
     let entry = self.get_entry(node_id)?;
 
     Ok(&entry.state)
   }
 
   pub fn set_entry_state(&mut self, node_id: &Uuid, state: &EntryState) -> Result<(), String> {
-    let entry = self.get_entry(node_id)?;
+    let mut version = self.next_version();
+
+    version.get_entry_mut(node_id)?.state = state.clone();
 
-    entry.state = state.clone();
+    let op = Op::SetState { id: self.next_op_id(), node_id: *node_id, state: state.clone() };
+
+    self.commit(version, vec![op]);
     Ok(())
   }
 
   pub fn entry_state_next(&mut self, node_id: &Uuid) -> Result<&EntryState, String> {
-    let entry = self.get_entry(node_id)?;
+    let mut version = self.next_version();
 
-    entry.state.next();
+    let next = version.get_entry(node_id)?.state.next();
+    version.get_entry_mut(node_id)?.state = next.clone();
 
-    Ok(&entry.state)
+    let op = Op::SetState { id: self.next_op_id(), node_id: *node_id, state: next };
+
+    self.commit(version, vec![op]);
+    self.entry_state(node_id)
   }
 
   pub fn entry_state_prev(&mut self, node_id: &Uuid) -> Result<&EntryState, String> {
-    let entry = self.get_entry(node_id)?;
+    let mut version = self.next_version();
 
-    entry.state.prev();
+    let prev = version.get_entry(node_id)?.state.prev();
+    version.get_entry_mut(node_id)?.state = prev.clone();
 
-    Ok(&entry.state)
+    let op = Op::SetState { id: self.next_op_id(), node_id: *node_id, state: prev };
+
+    self.commit(version, vec![op]);
+    self.entry_state(node_id)
+  }
+
+  // --- Derived stats & ordering ---
+
+  // Walks `id`'s subtree once, tallying every descendant entry's state.
+  // Linear in subtree size; relies on the arena's parent/child invariants
+  // (no cycles reachable through `first_child`/`next_sibling`) rather than
+  // tracking a visited set.
+  pub fn container_progress(&self, id: &Uuid) -> Result<Progress, String> {
+    let version = self.current();
+    let node_id = version.node_id(id)?;
+    if !version.slot(node_id).value.is_container() {Err(format!("Node {} must be a container", id))?}
+
+    let mut progress = Progress::default();
+    version.accumulate_progress(node_id, &mut progress);
+    Ok(progress)
+  }
+
+  // Reorders `id`'s children in place by `key`; `SortKey::Manual` leaves
+  // `order` (i.e. the arena's sibling links) untouched. Recorded as a run
+  // of `Reorder` ops anchored to each other, the same representation
+  // `move_node`/`swap_nodes` use, so a sort survives `Tree::merge` like any
+  // other structural edit.
+  pub fn sort_children(&mut self, id: &Uuid, key: SortKey) -> Result<(), String> {
+    if key == SortKey::Manual {return Ok(())}
+
+    let mut version = self.next_version();
+    let parent = version.node_id(id)?;
+    if !version.slot(parent).value.is_container() {Err(format!("Node {} must be a container", id))?}
+
+    let mut children = version.children(parent);
+    match key {
+      SortKey::State => children.sort_by_key(|&child| state_rank(&version.slot(child).value)),
+      SortKey::Name => children.sort_by(|&a, &b| version.slot(a).value.get_name().cmp(version.slot(b).value.get_name())),
+      SortKey::Manual => unreachable!(),
+    }
+    version.relink_children(parent, &children);
+
+    let ops: Vec<Op> = children.iter().enumerate().map(|(i, &child)| {
+      let node_id = *version.slot(child).value.get_id();
+      let after = if i == 0 {None} else {Some(*version.slot(children[i - 1]).value.get_id())};
+      Op::Reorder { id: self.next_op_id(), node_id, after }
+    }).collect();
+
+    self.commit(version, ops);
+    Ok(())
+  }
+
+  // --- Offline merge ---
+
+  // Folds `other`'s ops that `self` hasn't seen yet into `self`'s active
+  // log (not a discarded redo branch - see `active_log`), then replays the
+  // combined log from scratch to get a reconciled version. This assumes
+  // `self` and `other` are forks of the same tree (same root id, e.g. two
+  // offline copies of one exported checklist) rather than two unrelated
+  // trees - merging unrelated roots doesn't have a sensible result and is
+  // rejected.
+  pub fn merge(&mut self, other: &Tree) -> Result<(), String> {
+    if self.get_root_id() != other.get_root_id() {
+      Err("Can only merge trees that share a common root".to_string())?
+    }
+
+    let root_uuid = *self.get_root_id();
+    let root_node = self.get_node(&root_uuid)?.clone();
+
+    self.drop_redo_branch();
+
+    let seen: HashSet<OpId> = self.log.iter().map(Op::id).collect();
+    let mut combined = self.log.clone();
+    combined.extend(other.active_log().iter().filter(|op| !seen.contains(&op.id())).cloned());
+    combined.sort_by_key(Op::id);
+
+    let version = Self::replay(root_uuid, root_node, &combined)?;
+
+    // `other`'s ops may carry counters past our own - absorb the high-water
+    // mark so our next local op still sorts after everything we just merged
+    // in, keeping `next_op_id`'s `(counter, actor)` pairs a valid total order.
+    self.counter = combined.iter().map(|op| op.id().counter).max().unwrap_or(self.counter);
+
+    self.log = combined;
+    self.finish_commit(version);
+    Ok(())
+  }
+
+  // Serializes this tree's root and active op log (see `active_log`) to a
+  // JSON string - the save-file format `Tree::load` reads back. Two saved
+  // files round-tripped through `load` are forks of the same root in
+  // `merge`'s sense, so they can be reconciled with a plain `a.merge(&b)`
+  // after loading both.
+  pub fn save(&self) -> Result<String, String> {
+    let root_uuid = *self.get_root_id();
+    let root = self.get_node(&root_uuid)?.clone();
+
+    let saved = SavedTree { root, actor: self.actor, counter: self.counter, log: self.active_log().to_vec() };
+    serde_json::to_string(&saved).map_err(|e| e.to_string())
+  }
+
+  // Rebuilds a tree from a `Tree::save`d JSON string by replaying its op log
+  // from scratch.
+  pub fn load(data: &str) -> Result<Tree, String> {
+    let saved: SavedTree = serde_json::from_str(data).map_err(|e| e.to_string())?;
+    let root_uuid = *saved.root.get_id();
+
+    let version = Self::replay(root_uuid, saved.root, &saved.log)?;
+    let log_len = saved.log.len();
+
+    Ok(Self {
+      history: vec![version],
+      cursor: 0,
+      actor: saved.actor,
+      counter: saved.counter,
+      log: saved.log,
+      log_marks: vec![log_len],
+    })
+  }
+
+  // Rebuilds a tree from a totally-ordered op log - the merge-side
+  // counterpart to the mutating methods above. Works in plain Uuid space
+  // (a node table plus a children-by-uuid table) since ops don't carry
+  // arena slots, then hands the result to `Version::from_tree` to get an
+  // arena back. Ops are folded in `(counter, actor)` order, so concurrent
+  // `SetState`s naturally resolve last-writer-wins (the later op in the
+  // order simply overwrites the earlier one) and concurrent inserts at the
+  // same anchor interleave identically on every replica.
+  fn replay(root_id: Uuid, root_node: Node, log: &[Op]) -> Result<Version, String> {
+    let mut nodes: HashMap<Uuid, Node> = HashMap::new();
+    let mut children: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    let mut parent_of: HashMap<Uuid, Uuid> = HashMap::new();
+    let mut tombstoned: HashSet<Uuid> = HashSet::new();
+
+    nodes.insert(root_id, root_node);
+    children.insert(root_id, Vec::new());
+
+    for op in log {
+      match op {
+        Op::AddNode { parent, after, node_id, node, .. } => {
+          if tombstoned.contains(node_id) || !nodes.contains_key(parent) {continue}
+
+          nodes.insert(*node_id, node.clone());
+          children.entry(*node_id).or_default();
+          parent_of.insert(*node_id, *parent);
+          splice(&mut children, *parent, *node_id, *after);
+        }
+        Op::Remove { node_id, .. } => {
+          // Delete always wins: tombstone it so a concurrent op touching
+          // this node (replayed before or after, doesn't matter) is a
+          // no-op against an id that's no longer live.
+          tombstoned.insert(*node_id);
+          nodes.remove(node_id);
+
+          if let Some(parent_id) = parent_of.remove(node_id) {
+            if let Some(siblings) = children.get_mut(&parent_id) {
+              siblings.retain(|id| id != node_id);
+            }
+          }
+        }
+        Op::SetState { node_id, state, .. } => {
+          if let Some(Node::Entry(entry)) = nodes.get_mut(node_id) {
+            entry.state = state.clone();
+          }
+        }
+        Op::Reparent { node_id, new_parent, after, .. } => {
+          if tombstoned.contains(node_id) || !nodes.contains_key(new_parent) {continue}
+
+          if let Some(old_parent) = parent_of.get(node_id).copied() {
+            if let Some(siblings) = children.get_mut(&old_parent) {
+              siblings.retain(|id| id != node_id);
+            }
+          }
+
+          parent_of.insert(*node_id, *new_parent);
+          splice(&mut children, *new_parent, *node_id, *after);
+        }
+        Op::Reorder { node_id, after, .. } => {
+          if tombstoned.contains(node_id) {continue}
+
+          if let Some(parent_id) = parent_of.get(node_id).copied() {
+            splice(&mut children, parent_id, *node_id, *after);
+          }
+        }
+      }
+    }
+
+    Version::from_tree(root_id, nodes, children)
+  }
+}
+
+// Splices `node_id` into `parent_id`'s children right after `after` (or at
+// the front if `after` is `None`, or at the end if `after` is no longer
+// present) - the RGA rule that
+// makes a concurrent insert at the same anchor land in the same place on
+// every replica, since both sides replay the identical totally-ordered log.
+fn splice(children: &mut HashMap<Uuid, Vec<Uuid>>, parent_id: Uuid, node_id: Uuid, after: Option<Uuid>) {
+  let siblings = children.entry(parent_id).or_default();
+  siblings.retain(|&id| id != node_id);
+
+  let pos = match after {
+    Some(anchor) => siblings.iter().position(|&id| id == anchor).map(|i| i + 1).unwrap_or(siblings.len()),
+    None => 0,
+  };
+
+  siblings.insert(pos, node_id);
+}
+
+// --- Op log ---
+
+// A Lamport-stamped id: `counter` is bumped on every local op and `actor`
+// breaks ties between concurrent edits from different trees, giving a
+// total order across replicas without a central sequencer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+struct OpId {
+  counter: u64,
+  actor: Uuid,
+}
+
+// One structural or state edit, as recorded for `Tree::merge`. Carries
+// enough of its own context (parent, insertion anchor, the node value
+// itself for adds) to be replayed against a tree that never saw the
+// intervening local history.
+#[derive(Clone, Serialize, Deserialize)]
+enum Op {
+  AddNode { id: OpId, parent: Uuid, after: Option<Uuid>, node_id: Uuid, node: Node },
+  Remove { id: OpId, node_id: Uuid },
+  SetState { id: OpId, node_id: Uuid, state: EntryState },
+  Reparent { id: OpId, node_id: Uuid, new_parent: Uuid, after: Option<Uuid> },
+  Reorder { id: OpId, node_id: Uuid, after: Option<Uuid> },
+}
+
+// On-disk form of a `Tree`, written by `Tree::save` and read back by
+// `Tree::load` - the root node plus enough op-log state (`actor`, `counter`)
+// to keep stamping new ops consistently with whatever was saved.
+#[derive(Serialize, Deserialize)]
+struct SavedTree {
+  root: Node,
+  actor: Uuid,
+  counter: u64,
+  log: Vec<Op>,
+}
+
+impl Op {
+  fn id(&self) -> OpId {
+    match self {
+      Op::AddNode { id, .. }
+      | Op::Remove { id, .. }
+      | Op::SetState { id, .. }
+      | Op::Reparent { id, .. }
+      | Op::Reorder { id, .. } => *id,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn matches_state(state: &EntryState, other: &EntryState) -> bool {
+    matches!(
+      (state, other),
+      (EntryState::Pending, EntryState::Pending)
+        | (EntryState::InProgress, EntryState::InProgress)
+        | (EntryState::Completed, EntryState::Completed)
+        | (EntryState::Canceled, EntryState::Canceled)
+    )
+  }
+
+  // Reproduces the scenario from review: actor `b` races ahead on its own
+  // fork, actor `a` merges `b` in and then makes one more edit. That new
+  // edit is causally last, so it must still win once a third replica
+  // merges both histories - which only holds if `merge` bumps `a`'s
+  // counter past everything it just absorbed from `b`.
+  #[test]
+  fn merge_keeps_a_causally_later_edit_winning() {
+    let mut base = Tree::new(Container::new("root", ""));
+    let root_id = *base.get_root_id();
+    base.add_node(&root_id, Node::Entry(Entry::new("task", ""))).unwrap();
+    let task_id = *base.get_children_ids(&root_id).unwrap()[0];
+
+    let mut a = base.fork();
+    let mut b = base.fork();
+
+    for _ in 0..9 {
+      b.entry_state_next(&task_id).unwrap();
+    }
+
+    a.merge(&b).unwrap();
+    a.entry_state_next(&task_id).unwrap();
+    let a_final = a.entry_state(&task_id).unwrap().clone();
+
+    let mut c = base.fork();
+    c.merge(&a).unwrap();
+    c.merge(&b).unwrap();
+
+    assert!(matches_state(c.entry_state(&task_id).unwrap(), &a_final));
+  }
+
+  #[test]
+  fn fork_produces_an_independently_editable_copy_of_the_same_root() {
+    let tree = Tree::new(Container::new("root", ""));
+    let root_id = *tree.get_root_id();
+
+    let mut fork = tree.fork();
+    assert_eq!(fork.get_root_id(), &root_id);
+
+    fork.add_node(&root_id, Node::Entry(Entry::new("task", ""))).unwrap();
+    assert!(tree.get_children_ids(&root_id).unwrap().is_empty());
+    assert_eq!(fork.get_children_ids(&root_id).unwrap().len(), 1);
+  }
+
+  // The offline-sync path the request asked for: edit two copies
+  // independently, save both to strings, load them back, and merge -
+  // without ever sharing the original in-memory `Tree`s.
+  #[test]
+  fn saved_logs_round_trip_and_merge() {
+    let mut base = Tree::new(Container::new("root", ""));
+    let root_id = *base.get_root_id();
+    base.add_node(&root_id, Node::Entry(Entry::new("task", ""))).unwrap();
+    let task_id = *base.get_children_ids(&root_id).unwrap()[0];
+
+    let mut a = base.fork();
+    let mut b = base.fork();
+    a.entry_state_next(&task_id).unwrap();
+    b.add_node(&root_id, Node::Entry(Entry::new("second task", ""))).unwrap();
+
+    let mut loaded_a = Tree::load(&a.save().unwrap()).unwrap();
+    let loaded_b = Tree::load(&b.save().unwrap()).unwrap();
+
+    loaded_a.merge(&loaded_b).unwrap();
+
+    assert!(matches_state(loaded_a.entry_state(&task_id).unwrap(), a.entry_state(&task_id).unwrap()));
+    assert_eq!(loaded_a.get_children_ids(&root_id).unwrap().len(), 2);
+  }
+
+  // `undo`/`redo` just move `cursor` across `history` - no replay, no log
+  // surgery. A round trip back to the same cursor must land on the exact
+  // state the edit produced, state included.
+  #[test]
+  fn undo_then_redo_round_trips_node_state() {
+    let mut tree = Tree::new(Container::new("root", ""));
+    let root_id = *tree.get_root_id();
+    tree.add_node(&root_id, Node::Entry(Entry::new("task", ""))).unwrap();
+    let task_id = *tree.get_children_ids(&root_id).unwrap()[0];
+
+    tree.entry_state_next(&task_id).unwrap();
+    let after_edit = tree.entry_state(&task_id).unwrap().clone();
+
+    tree.undo().unwrap();
+    assert!(matches_state(tree.entry_state(&task_id).unwrap(), &EntryState::Pending));
+
+    tree.redo().unwrap();
+    assert!(matches_state(tree.entry_state(&task_id).unwrap(), &after_edit));
+  }
+
+  // Undo has to reach back across structural edits too, not just state
+  // changes - `remove_node`'s cascade delete and `change_parent`'s reparent
+  // both need to be fully undone, not just their top-level op.
+  #[test]
+  fn undo_reverts_remove_node_and_change_parent() {
+    let mut tree = Tree::new(Container::new("root", ""));
+    let root_id = *tree.get_root_id();
+    tree.add_node(&root_id, Node::Container(Container::new("folder", ""))).unwrap();
+    let folder_id = *tree.get_children_ids(&root_id).unwrap()[0];
+    tree.add_node(&folder_id, Node::Entry(Entry::new("child", ""))).unwrap();
+    let child_id = *tree.get_children_ids(&folder_id).unwrap()[0];
+
+    tree.change_parent(&root_id, &child_id).unwrap();
+    assert_eq!(tree.get_parent_id(&child_id).unwrap(), &root_id);
+    tree.undo().unwrap();
+    assert_eq!(tree.get_parent_id(&child_id).unwrap(), &folder_id);
+
+    tree.remove_node(&folder_id).unwrap();
+    assert!(tree.get_node(&folder_id).is_err());
+    assert!(tree.get_node(&child_id).is_err());
+    tree.undo().unwrap();
+    assert!(tree.get_node(&folder_id).is_ok());
+    assert_eq!(tree.get_parent_id(&child_id).unwrap(), &folder_id);
+  }
+
+  // The structural-sharing claim itself: a `Version` pinned from before a
+  // mutation must keep reading its old state even after `Tree` has moved
+  // its cursor on to a new one - `next_version`/`slot_mut` path-copy the
+  // touched slots into the *new* version rather than mutating the old
+  // one's `Rc<ArenaNode>`s in place.
+  #[test]
+  fn an_old_version_is_unaffected_by_a_later_mutation() {
+    let mut tree = Tree::new(Container::new("root", ""));
+    let root_id = *tree.get_root_id();
+    tree.add_node(&root_id, Node::Entry(Entry::new("task", ""))).unwrap();
+    let task_id = *tree.get_children_ids(&root_id).unwrap()[0];
+
+    let before = tree.current().clone();
+    assert!(matches_state(&before.get_entry(&task_id).unwrap().state, &EntryState::Pending));
+
+    tree.entry_state_next(&task_id).unwrap();
+    assert!(matches_state(tree.entry_state(&task_id).unwrap(), &EntryState::InProgress));
+
+    assert!(matches_state(&before.get_entry(&task_id).unwrap().state, &EntryState::Pending));
   }
 }
@@ -1,11 +1,15 @@
 use std::io::{self, Write};
 use tui::draw;
+use data::{Container, Tree};
 
 mod tui;
 mod data;
 
 fn main() -> io::Result<()> {
-  draw()?;
+  let root = Container::new("Checklist", "");
+  let mut tree = Tree::new(root);
+
+  draw(&mut tree)?;
 
   Ok(())
 }
@@ -1,6 +1,7 @@
 #![allow(unused_imports, dead_code, unused_variables)]
 use crossterm::{
   execute, queue,
+  event::{self, Event, KeyCode, KeyEvent},
   terminal, cursor, style::{self, Stylize, Print, ResetColor}
 };
 
@@ -11,9 +12,85 @@ use uuid::Uuid;
 
 use crate::data::{Node, EntryState, Entry, Container, Tree};
 
-pub fn draw() -> io::Result<()> {
+// Reads key events until the user quits, dispatching each into a `TreeOp`
+// against `tree` and re-rendering whenever something changes.
+pub fn draw(tree: &mut Tree) -> io::Result<()> {
   let mut stdout = io::stdout();
+  let mut app = AppState::new(*tree.get_root_id());
+  let mut visible = Vec::new();
 
+  terminal::enable_raw_mode()?;
+
+  let result = (|| -> io::Result<()> {
+    refresh_visible(tree, &app.tree_view, &mut visible)?;
+    render(&mut stdout, tree, &app.tree_view, &visible, status_line(&app).as_deref())?;
+
+    loop {
+      let key = match event::read()? {
+        Event::Key(key) => key,
+        _ => continue,
+      };
+
+      if app.current_screen == AppScreen::Filter {
+        apply_filter_key(&mut app, key).map_err(io::Error::other)?;
+        refresh_visible(tree, &app.tree_view, &mut visible)?;
+        render(&mut stdout, tree, &app.tree_view, &visible, status_line(&app).as_deref())?;
+        continue;
+      }
+
+      if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {break}
+
+      if app.current_screen == AppScreen::TreeView && key.code == KeyCode::Char('/') {
+        app.current_screen = AppScreen::Filter;
+        app.filter_buffer.clear();
+        render(&mut stdout, tree, &app.tree_view, &visible, status_line(&app).as_deref())?;
+        continue;
+      }
+
+      let op = match key_to_op(key) {
+        Some(op) => op,
+        None => continue,
+      };
+
+      if app.current_screen != AppScreen::TreeView {continue}
+
+      let result = apply_tree_op(tree, &mut app.tree_view, &visible, op)
+        .map_err(io::Error::other)?;
+
+      match result {
+        EventResult::Exit => break,
+        EventResult::Consumed { redraw: true } => {
+          refresh_visible(tree, &app.tree_view, &mut visible)?;
+          render(&mut stdout, tree, &app.tree_view, &visible, status_line(&app).as_deref())?;
+        }
+        EventResult::Consumed { redraw: false } | EventResult::Ignored => {}
+      }
+    }
+
+    Ok(())
+  })();
+
+  terminal::disable_raw_mode()?;
+  result
+}
+
+fn refresh_visible(tree: &Tree, view: &TreeViewState, visible: &mut Vec<(Uuid, usize)>) -> io::Result<()> {
+  visible.clear();
+
+  let root_id = *tree.get_root_id();
+  build_visible_nodes(tree, view, &root_id, 0, visible)
+    .map_err(io::Error::other)?;
+
+  Ok(())
+}
+
+fn render(
+  stdout: &mut io::Stdout,
+  tree: &Tree,
+  view: &TreeViewState,
+  visible: &[(Uuid, usize)],
+  status: Option<&str>
+) -> io::Result<()> {
   execute!(stdout, terminal::Clear(terminal::ClearType::All))?;
 
   for y in 0..40 {
@@ -27,10 +104,47 @@ pub fn draw() -> io::Result<()> {
     }
   }
 
+  for (row, (id, depth)) in visible.iter().enumerate() {
+    let y = row as u16 + 1;
+    if y as usize >= 39 {break}
+
+    let node = tree.get_node(id).ok();
+    let name = node.map(|node| node.get_name().to_string()).unwrap_or_default();
+
+    let progress = match node {
+      Some(node) if node.is_container() => tree.container_progress(id).ok(),
+      _ => None,
+    };
+    let suffix = progress.map(|p| {
+      let (done, total) = p.ratio();
+      format!(" ({}/{})", done, total)
+    }).unwrap_or_default();
+
+    let line = format!("{}{}{}", "  ".repeat(*depth), name, suffix);
+
+    let is_selected = *view.get_selected() == Some(*id);
+    let content = if is_selected {line.reverse()} else {line.stylize()};
+
+    queue!(stdout, cursor::MoveTo(2, y), style::PrintStyledContent(content))?;
+  }
+
+  if let Some(status) = status {
+    queue!(stdout, cursor::MoveTo(2, 0), style::PrintStyledContent(status.to_string().stylize()))?;
+  }
+
   stdout.flush()?;
   Ok(())
 }
 
+// What to show on the top border: the in-progress query while typing into
+// `AppScreen::Filter`, the active filter once one's applied, or nothing.
+fn status_line(app: &AppState) -> Option<String> {
+  match app.current_screen {
+    AppScreen::Filter => Some(format!("/{}", app.filter_buffer)),
+    _ => app.tree_view.get_filter().as_ref().map(|query| format!("filter: {}", query)),
+  }
+}
+
 // --- Actual Code ---
 
 // States and data
@@ -39,22 +153,49 @@ pub enum AppScreen {
   TreeSelect,
   TreeView,
   Settings,
+  // Editing `AppState::filter_buffer`, entered from `TreeView` with `/`.
+  Filter,
 }
 
 pub struct AppState {
   current_screen: AppScreen,
   trees: Vec<Uuid>,
   selected_tree: Option<Uuid>,
-  tree_view: TreeViewState
+  tree_view: TreeViewState,
+  // The query being typed while `current_screen == AppScreen::Filter`,
+  // applied to `tree_view`'s filter on `Enter` (see `apply_filter_key`).
+  filter_buffer: String,
+}
+
+impl AppState {
+  pub fn new(tree_id: Uuid) -> Self {
+    Self {
+      current_screen: AppScreen::TreeView,
+      trees: vec![tree_id],
+      selected_tree: Some(tree_id),
+      tree_view: TreeViewState::new(),
+      filter_buffer: String::new(),
+    }
+  }
 }
 
 pub struct TreeViewState {
   selected: Option<Uuid>,
   collapsed: HashSet<Uuid>,
-  pub scroll_offset: usize
+  pub scroll_offset: usize,
+  filter: Option<String>
 }
 
 impl TreeViewState {
+  pub fn new() -> Self {
+    Self {
+      selected: None,
+      collapsed: HashSet::new(),
+      scroll_offset: 0,
+      filter: None,
+    }
+  }
+
   pub fn get_selected(&self) -> &Option<Uuid> {
     &self.selected
   }
@@ -73,7 +214,7 @@ impl TreeViewState {
     Ok(&self.collapsed)
   }
 
-  pub fn add_collapsed(&mut self, tree: &mut Tree, id: &Uuid) -> Result<(), String> {
+  pub fn add_collapsed(&mut self, tree: &Tree, id: &Uuid) -> Result<(), String> {
     if tree.get_node(id)?.is_entry() {Err("Node must be a container to collaps")?}
 
     self.collapsed.insert(*id);
@@ -88,26 +229,399 @@ impl TreeViewState {
   pub fn is_collapsed(&self, id: &Uuid) -> Result<bool, String> {
     Ok(self.collapsed.contains(id))
   }
+
+  pub fn get_filter(&self) -> &Option<String> {
+    &self.filter
+  }
+
+  pub fn set_filter<S: Into<String>>(&mut self, query: S) -> Result<(), String> {
+    self.filter = Some(query.into());
+    Ok(())
+  }
+
+  pub fn clear_filter(&mut self) -> Result<(), String> {
+    self.filter = None;
+    Ok(())
+  }
+
+  // Case-insensitive substring match against a node's name/desc, used to
+  // decide whether the node itself counts as a filter hit.
+  fn matches_filter(&self, tree: &Tree, id: &Uuid) -> Result<bool, String> {
+    let query = match &self.filter {
+      Some(q) if !q.is_empty() => q.to_lowercase(),
+      _ => return Ok(true),
+    };
+
+    let node = tree.get_node(id)?;
+    let name_match = node.get_name().to_lowercase().contains(&query);
+    let desc_match = node.get_desc().to_lowercase().contains(&query);
+
+    Ok(name_match || desc_match)
+  }
 }
 
 // --- General Functions ---
+
+// Flattens the tree into display order, honouring both collapsed containers
+// and an active `TreeViewState` filter. Returns whether `current_id` or any
+// of its descendants matched the filter, so a container-matching ancestor
+// knows to bypass `is_collapsed` and stay expanded.
 pub fn build_visible_nodes(
   tree: &Tree,
   view: &TreeViewState,
   current_id: &Uuid,
   depth: usize,
   out: &mut Vec<(Uuid, usize)>
-) -> Result<(), String> {
+) -> Result<bool, String> {
+  let self_match = view.matches_filter(tree, current_id)?;
+
+  if tree.get_node(current_id)?.is_entry() {
+    if view.get_filter().is_none() || self_match {
+      out.push((*current_id, depth));
+    }
+
+    return Ok(self_match);
+  }
+
+  let is_collapsed = view.is_collapsed(current_id)?;
+
+  if is_collapsed && view.get_filter().is_none() {
+    out.push((*current_id, depth));
+    return Ok(self_match);
+  }
+
+  let child_ids: Vec<Uuid> = tree.get_children_ids(current_id)?.into_iter().cloned().collect();
+
+  let mut child_nodes = Vec::new();
+  let mut descendant_match = false;
+  for child_id in &child_ids {
+    let matched = build_visible_nodes(tree, view, child_id, depth + 1, &mut child_nodes)?;
+    descendant_match = descendant_match || matched;
+  }
+
+  let subtree_match = self_match || descendant_match;
+  if view.get_filter().is_some() && !subtree_match {return Ok(false)}
+
   out.push((*current_id, depth));
 
-  if view.is_collapsed(current_id)? {return Ok(())}
+  if is_collapsed && !descendant_match {
+    // Collapsed, and only the container itself matched (if at all) - keep
+    // its children hidden.
+    return Ok(subtree_match);
+  }
+
+  out.extend(child_nodes);
+  Ok(subtree_match)
+}
 
-  let children = tree.get_children_ids(current_id)?;
-  for child_id in children {
-    build_visible_nodes(tree, view, child_id, depth + 1, out)?;
+// --- Event Dispatch ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+  Up,
+  Down,
+  Left,
+  Right,
+}
+
+// One user-facing operation against the tree. The event loop maps raw key
+// events onto this enum; `apply_tree_op` is the only place that actually
+// mutates `Tree`/`TreeViewState` in response to one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreeOp {
+  MoveSelection(Direction),
+  ToggleCollapse,
+  CycleStateNext,
+  CycleStatePrev,
+  AddEntry,
+  AddContainer,
+  Delete,
+  Reparent,
+  Undo,
+  Redo,
+}
+
+// Whether a dispatched op changed anything visible, and whether the loop
+// should keep running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventResult {
+  Ignored,
+  Consumed { redraw: bool },
+  Exit,
+}
+
+fn key_to_op(key: KeyEvent) -> Option<TreeOp> {
+  use KeyCode::*;
+
+  match key.code {
+    Up | Char('k') => Some(TreeOp::MoveSelection(Direction::Up)),
+    Down | Char('j') => Some(TreeOp::MoveSelection(Direction::Down)),
+    Left | Char('h') => Some(TreeOp::MoveSelection(Direction::Left)),
+    Right | Char('l') => Some(TreeOp::MoveSelection(Direction::Right)),
+    Tab | Char(' ') => Some(TreeOp::ToggleCollapse),
+    Char('+') => Some(TreeOp::CycleStateNext),
+    Char('-') => Some(TreeOp::CycleStatePrev),
+    Char('a') => Some(TreeOp::AddEntry),
+    Char('A') => Some(TreeOp::AddContainer),
+    Char('d') | Delete => Some(TreeOp::Delete),
+    Char('r') => Some(TreeOp::Reparent),
+    Char('u') => Some(TreeOp::Undo),
+    Char('U') => Some(TreeOp::Redo),
+    _ => None,
+  }
+}
+
+// Handles a key event while `AppScreen::Filter` is active: edits
+// `app.filter_buffer`, or on `Enter`/`Esc` leaves the screen and applies
+// (or cancels) it against `app.tree_view`'s filter. An empty buffer on
+// `Enter` clears the filter rather than setting an always-matching one.
+fn apply_filter_key(app: &mut AppState, key: KeyEvent) -> Result<(), String> {
+  match key.code {
+    KeyCode::Enter => {
+      let query = std::mem::take(&mut app.filter_buffer);
+      if query.is_empty() {
+        app.tree_view.clear_filter()?;
+      } else {
+        app.tree_view.set_filter(query)?;
+      }
+      app.current_screen = AppScreen::TreeView;
+    }
+    KeyCode::Esc => {
+      app.filter_buffer.clear();
+      app.current_screen = AppScreen::TreeView;
+    }
+    KeyCode::Backspace => {
+      app.filter_buffer.pop();
+    }
+    KeyCode::Char(c) => app.filter_buffer.push(c),
+    _ => {}
   }
 
   Ok(())
 }
 
+fn apply_tree_op(
+  tree: &mut Tree,
+  view: &mut TreeViewState,
+  visible: &[(Uuid, usize)],
+  op: TreeOp
+) -> Result<EventResult, String> {
+  match op {
+    TreeOp::MoveSelection(dir) => move_selection(tree, view, visible, dir),
+    TreeOp::ToggleCollapse => toggle_collapse(tree, view),
+    TreeOp::CycleStateNext => cycle_entry_state(tree, view, true),
+    TreeOp::CycleStatePrev => cycle_entry_state(tree, view, false),
+    TreeOp::AddEntry => insert_node(tree, view, Node::Entry(Entry::new("New entry", ""))),
+    TreeOp::AddContainer => insert_node(tree, view, Node::Container(Container::new("New container", ""))),
+    TreeOp::Delete => delete_selected(tree, view),
+    TreeOp::Reparent => reparent_up(tree, view),
+    TreeOp::Undo => {
+      match tree.undo() {
+        Ok(()) => reconcile_selection(tree, view),
+        Err(_) => Ok(EventResult::Ignored),
+      }
+    }
+    TreeOp::Redo => {
+      match tree.redo() {
+        Ok(()) => reconcile_selection(tree, view),
+        Err(_) => Ok(EventResult::Ignored),
+      }
+    }
+  }
+}
+
+// After jumping to a different version, the previously-selected node may no
+// longer exist (e.g. an undone `AddEntry`) - drop the selection rather than
+// let later lookups fail against a version that no longer has it.
+fn reconcile_selection(tree: &Tree, view: &mut TreeViewState) -> Result<EventResult, String> {
+  if let Some(id) = *view.get_selected() {
+    if tree.get_node(&id).is_err() {
+      view.deselect()?;
+    }
+  }
+
+  Ok(EventResult::Consumed { redraw: true })
+}
+
+// Moves the selection over the flattened, already-visible order: up/down
+// step to the previous/next row, left collapses a container (or ascends to
+// the parent if there's nothing to collapse), right expands a collapsed
+// container (or steps into its first child).
+fn move_selection(
+  tree: &Tree,
+  view: &mut TreeViewState,
+  visible: &[(Uuid, usize)],
+  dir: Direction
+) -> Result<EventResult, String> {
+  if visible.is_empty() {return Ok(EventResult::Ignored)}
+
+  let current = view.get_selected()
+    .and_then(|id| visible.iter().position(|(node_id, _)| *node_id == id));
+
+  match dir {
+    Direction::Down => {
+      let next = match current {
+        Some(i) if i + 1 < visible.len() => i + 1,
+        Some(i) => i,
+        None => 0,
+      };
+
+      view.select(&visible[next].0)?;
+      Ok(EventResult::Consumed { redraw: true })
+    }
+
+    Direction::Up => {
+      let prev = match current {
+        Some(i) if i > 0 => i - 1,
+        Some(i) => i,
+        None => 0,
+      };
+
+      view.select(&visible[prev].0)?;
+      Ok(EventResult::Consumed { redraw: true })
+    }
+
+    Direction::Left => {
+      let i = match current {
+        Some(i) => i,
+        None => return Ok(EventResult::Ignored),
+      };
+      let id = visible[i].0;
+
+      if tree.get_node(&id)?.is_container() && !view.is_collapsed(&id)? {
+        view.add_collapsed(tree, &id)?;
+        return Ok(EventResult::Consumed { redraw: true });
+      }
+
+      match tree.get_parent_id(&id) {
+        Ok(parent_id) => {
+          let parent_id = *parent_id;
+          view.select(&parent_id)?;
+          Ok(EventResult::Consumed { redraw: true })
+        }
+        Err(_) => Ok(EventResult::Ignored),
+      }
+    }
+
+    Direction::Right => {
+      let i = match current {
+        Some(i) => i,
+        None => return Ok(EventResult::Ignored),
+      };
+      let id = visible[i].0;
+
+      if tree.get_node(&id)?.is_container() {
+        if view.is_collapsed(&id)? {
+          view.remove_collapsed(&id)?;
+          return Ok(EventResult::Consumed { redraw: true });
+        }
+
+        if let Some((child_id, child_depth)) = visible.get(i + 1) {
+          if *child_depth > visible[i].1 {
+            view.select(child_id)?;
+            return Ok(EventResult::Consumed { redraw: true });
+          }
+        }
+      }
+
+      Ok(EventResult::Ignored)
+    }
+  }
+}
+
+fn toggle_collapse(tree: &Tree, view: &mut TreeViewState) -> Result<EventResult, String> {
+  let id = match view.get_selected() {
+    Some(id) => *id,
+    None => return Ok(EventResult::Ignored),
+  };
+
+  if !tree.get_node(&id)?.is_container() {return Ok(EventResult::Ignored)}
+
+  if view.is_collapsed(&id)? {
+    view.remove_collapsed(&id)?;
+  } else {
+    view.add_collapsed(tree, &id)?;
+  }
+
+  Ok(EventResult::Consumed { redraw: true })
+}
+
+fn cycle_entry_state(tree: &mut Tree, view: &TreeViewState, forward: bool) -> Result<EventResult, String> {
+  let id = match view.get_selected() {
+    Some(id) => *id,
+    None => return Ok(EventResult::Ignored),
+  };
+
+  if !tree.get_node(&id)?.is_entry() {return Ok(EventResult::Ignored)}
+
+  if forward {
+    tree.entry_state_next(&id)?;
+  } else {
+    tree.entry_state_prev(&id)?;
+  }
+
+  Ok(EventResult::Consumed { redraw: true })
+}
+
+// A container targets itself, an entry targets its parent, and nothing
+// selected falls back to the tree root.
+fn insert_target(tree: &Tree, view: &TreeViewState) -> Result<Uuid, String> {
+  let id = match view.get_selected() {
+    Some(id) => *id,
+    None => return Ok(*tree.get_root_id()),
+  };
+
+  if tree.get_node(&id)?.is_container() {
+    Ok(id)
+  } else {
+    Ok(*tree.get_parent_id(&id)?)
+  }
+}
+
+fn insert_node(tree: &mut Tree, view: &mut TreeViewState, node: Node) -> Result<EventResult, String> {
+  let parent_id = insert_target(tree, view)?;
+  let node_id = *node.get_id();
+
+  tree.add_node(&parent_id, node)?;
+  view.select(&node_id)?;
+
+  Ok(EventResult::Consumed { redraw: true })
+}
+
+fn delete_selected(tree: &mut Tree, view: &mut TreeViewState) -> Result<EventResult, String> {
+  let id = match view.get_selected() {
+    Some(id) => *id,
+    None => return Ok(EventResult::Ignored),
+  };
+
+  if id == *tree.get_root_id() {return Ok(EventResult::Ignored)}
+
+  let parent_id = *tree.get_parent_id(&id)?;
+
+  tree.remove_node(&id)?;
+  view.remove_collapsed(&id)?;
+  view.select(&parent_id)?;
+
+  Ok(EventResult::Consumed { redraw: true })
+}
+
+// Moves the selected node one level up, into its grandparent container.
+// There's no interactive picker yet, so `Reparent` always targets the
+// grandparent; it's a no-op once the parent is already the root.
+fn reparent_up(tree: &mut Tree, view: &mut TreeViewState) -> Result<EventResult, String> {
+  let id = match view.get_selected() {
+    Some(id) => *id,
+    None => return Ok(EventResult::Ignored),
+  };
+
+  let parent_id = *tree.get_parent_id(&id)?;
+
+  let grandparent_id = match tree.get_parent_id(&parent_id) {
+    Ok(id) => *id,
+    Err(_) => return Ok(EventResult::Ignored),
+  };
+
+  tree.change_parent(&grandparent_id, &id)?;
+  Ok(EventResult::Consumed { redraw: true })
+}
 